@@ -1,25 +1,83 @@
+use criterion::measurement::Measurement;
 use criterion::{criterion_group, criterion_main, Criterion};
 use nickel_lang::term::Term;
-use nickel_lang_utilities::{bench_expect, EvalMode};
+use nickel_lang_utilities::{bench_expect, bench_expect_group, stabilize_env, EvalMode};
+#[cfg(not(feature = "cpb"))]
 use pprof::criterion::{Output, PProfProfiler};
 
-fn church(c: &mut Criterion) {
+#[cfg(feature = "mimalloc")]
+#[global_allocator]
+static GLOBAL: mimalloc::MiMalloc = mimalloc::MiMalloc;
+
+fn church<M: Measurement + 'static>(c: &mut Criterion<M>) {
+    let expect = |term| matches!(term, Term::Bool(true));
+    bench_expect_group(
+        "church",
+        env!("CARGO_MANIFEST_DIR"),
+        "functions/church",
+        None,
+        [3, 5, 10, 100],
+        EvalMode::Normal,
+        expect,
+        c,
+    );
+}
+
+fn church_phases<M: Measurement + 'static>(c: &mut Criterion<M>) {
     let expect = |term| matches!(term, Term::Bool(true));
     bench_expect(
-        "church 3",
+        "church 3 parse",
         env!("CARGO_MANIFEST_DIR"),
         "functions/church",
         None,
         3,
-        EvalMode::Normal,
+        EvalMode::ParseOnly,
+        |_| true,
+        c,
+    );
+    bench_expect(
+        "church 3 typecheck",
+        env!("CARGO_MANIFEST_DIR"),
+        "functions/church",
+        None,
+        3,
+        EvalMode::Typecheck,
+        |_| true,
+        c,
+    );
+    bench_expect(
+        "church 3 eval",
+        env!("CARGO_MANIFEST_DIR"),
+        "functions/church",
+        None,
+        3,
+        EvalMode::Eval,
         expect,
         c,
     );
 }
 
+#[cfg(not(feature = "cpb"))]
+criterion_group! {
+    name = benches;
+    config = { stabilize_env(); Criterion::default() }.with_profiler(PProfProfiler::new(100, Output::Flamegraph(None)));
+    targets = church, church_phases
+}
+
+#[cfg(all(feature = "cpb", any(target_arch = "x86", target_arch = "x86_64")))]
+criterion_group! {
+    name = benches;
+    config = { stabilize_env(); Criterion::default() }.with_measurement(criterion_cycles_per_byte::CyclesPerByte);
+    targets = church, church_phases
+}
+
+// `criterion_cycles_per_byte` only supports x86/x86_64. On other targets the
+// `cpb` feature falls back to the default wall-clock measurement so the benches
+// still build and run.
+#[cfg(all(feature = "cpb", not(any(target_arch = "x86", target_arch = "x86_64"))))]
 criterion_group! {
     name = benches;
-    config = Criterion::default().with_profiler(PProfProfiler::new(100, Output::Flamegraph(None)));
-    targets = church
+    config = { stabilize_env(); Criterion::default() };
+    targets = church, church_phases
 }
 criterion_main!(benches);
@@ -0,0 +1,190 @@
+//! Benchmarking helpers shared by Nickel's criterion benches.
+//!
+//! The central entry point is [`bench_expect`], which loads a Nickel source
+//! file, evaluates it and checks the result against a caller-provided
+//! predicate. Results are reported per source byte through criterion's
+//! [`Throughput`] so programs of very different sizes stay comparable, and the
+//! `Criterion` instance is left generic over its [`Measurement`] so callers can
+//! plug in the `pprof` profiler or CPU-cycles-per-byte measurement.
+
+use std::io::Cursor;
+use std::path::PathBuf;
+
+use criterion::measurement::Measurement;
+use criterion::{black_box, BatchSize, Bencher, BenchmarkId, Criterion, Throughput};
+
+use nickel_lang::cache::{Cache, ErrorTolerance};
+use nickel_lang::program::Program;
+use nickel_lang::term::Term;
+
+/// Which pipeline stage a benchmark should measure.
+///
+/// Isolating the stages lets a regression be attributed to parsing, typechecking
+/// or evaluation rather than lumping the whole pipeline into one number.
+pub enum EvalMode {
+    /// Parse, typecheck and evaluate to weak head normal form in one go.
+    Normal,
+    /// Measure parsing only.
+    ParseOnly,
+    /// Measure typechecking, the parsing happening as part of the measured work.
+    Typecheck,
+    /// Measure evaluation to weak head normal form of an already typechecked
+    /// program.
+    Eval,
+    /// Like [`EvalMode::Eval`] but force the result all the way to normal form.
+    DeepSeq,
+}
+
+/// Install a stable benchmarking environment before criterion takes its
+/// measurements.
+///
+/// Pins the current thread to a fixed core so results aren't perturbed by
+/// thread migration, degrading gracefully when affinity isn't available. The
+/// companion `mimalloc` cargo feature swaps in a consistent global allocator;
+/// that wiring lives in each bench binary since `#[global_allocator]` must be
+/// declared in the final crate. Call it once from the `criterion_group!`
+/// config so every bench binary shares the same stabilized environment.
+pub fn stabilize_env() {
+    if let Some(core) = core_affinity::get_core_ids().and_then(|mut ids| ids.pop()) {
+        core_affinity::set_for_current(core);
+    }
+}
+
+/// Load `<base_dir>/benches/<subpath>.ncl` and return the source that will be
+/// fed to the evaluator, together with the fixture's length in bytes.
+///
+/// The fixture is a record; we `import` it and apply its entry point (the
+/// `subtest` field, defaulting to `run`) to the size parameter, which is how
+/// the size is injected into the evaluated program.
+fn prepare_source(
+    base_dir: &str,
+    subpath: &str,
+    subtest: Option<&str>,
+    iteration: u32,
+) -> (String, u64) {
+    let mut path = PathBuf::from(base_dir);
+    path.push("benches");
+    path.push(format!("{subpath}.ncl"));
+    let fixture = std::fs::read_to_string(&path).expect("benchmark fixture failed to load");
+
+    let entry = subtest.unwrap_or("run");
+    let source = format!("(import \"{}\").{entry} {iteration}", path.display());
+    (source, fixture.len() as u64)
+}
+
+/// Build a fresh [`Program`] from an in-memory source string.
+fn program(source: &str, name: &str) -> Program {
+    Program::new_from_source(Cursor::new(source.to_owned()), name)
+        .expect("benchmark source failed to load")
+}
+
+/// Build and typecheck a program so that [`EvalMode::Eval`] measures
+/// evaluation of an already prepared term in isolation.
+fn typechecked(source: &str, name: &str) -> Program {
+    let mut p = program(source, name);
+    p.typecheck().expect("benchmark source failed to typecheck");
+    p
+}
+
+/// Benchmark a single Nickel program, reporting time per source byte.
+///
+/// `c` is generic over the criterion [`Measurement`], so the same helper works
+/// with wall-clock nanoseconds, the `pprof` profiler or, behind the `cpb`
+/// feature, CPU cycles per byte.
+pub fn bench_expect<M, F>(
+    name: &str,
+    base_dir: &str,
+    subpath: &str,
+    subtest: Option<&str>,
+    iteration: u32,
+    eval_mode: EvalMode,
+    expect: F,
+    c: &mut Criterion<M>,
+) where
+    M: Measurement + 'static,
+    F: Fn(Term) -> bool,
+{
+    let (source, bytes) = prepare_source(base_dir, subpath, subtest, iteration);
+
+    let mut group = c.benchmark_group(name);
+    group.throughput(Throughput::Bytes(bytes));
+    group.bench_function(BenchmarkId::from_parameter(iteration), |b| {
+        run_phase(b, &source, name, &eval_mode, &expect);
+    });
+    group.finish();
+}
+
+/// Benchmark the same program across a range of sizes in one criterion group.
+///
+/// Each size in `sizes` is substituted into the source (the same injection
+/// [`bench_expect`] performs) and registered as a separate
+/// [`BenchmarkId::from_parameter`] point, so super-linear blowups in the
+/// evaluator show up as a curve in the group rather than a single number.
+pub fn bench_expect_group<M, I, F>(
+    name: &str,
+    base_dir: &str,
+    subpath: &str,
+    subtest: Option<&str>,
+    sizes: I,
+    eval_mode: EvalMode,
+    expect: F,
+    c: &mut Criterion<M>,
+) where
+    M: Measurement + 'static,
+    I: IntoIterator<Item = u32>,
+    F: Fn(Term) -> bool,
+{
+    let mut group = c.benchmark_group(name);
+    for n in sizes {
+        let (source, bytes) = prepare_source(base_dir, subpath, subtest, n);
+        group.throughput(Throughput::Bytes(bytes));
+        group.bench_with_input(BenchmarkId::from_parameter(n), &n, |b, _| {
+            run_phase(b, &source, name, &eval_mode, &expect);
+        });
+    }
+    group.finish();
+}
+
+/// Drive the measured loop for a single [`EvalMode`].
+fn run_phase<M, F>(
+    b: &mut Bencher<'_, M>,
+    source: &str,
+    name: &str,
+    eval_mode: &EvalMode,
+    expect: &F,
+) where
+    M: Measurement,
+    F: Fn(Term) -> bool,
+{
+    match eval_mode {
+        EvalMode::ParseOnly => b.iter_batched(
+            || source.to_owned(),
+            |src| {
+                let mut cache = Cache::new(ErrorTolerance::Strict);
+                let id = cache.add_string(name.to_owned(), src);
+                black_box(cache.parse(id).unwrap());
+            },
+            BatchSize::SmallInput,
+        ),
+        EvalMode::Typecheck => b.iter_batched(
+            || program(source, name),
+            |mut p| p.typecheck().unwrap(),
+            BatchSize::LargeInput,
+        ),
+        EvalMode::Eval => b.iter_batched(
+            || typechecked(source, name),
+            |mut p| assert!(expect(p.eval().unwrap().term.into_owned())),
+            BatchSize::LargeInput,
+        ),
+        EvalMode::DeepSeq => b.iter_batched(
+            || typechecked(source, name),
+            |mut p| assert!(expect(p.eval_full().unwrap().term.into_owned())),
+            BatchSize::LargeInput,
+        ),
+        EvalMode::Normal => b.iter_batched(
+            || program(source, name),
+            |mut p| assert!(expect(p.eval().unwrap().term.into_owned())),
+            BatchSize::LargeInput,
+        ),
+    }
+}